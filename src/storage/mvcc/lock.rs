@@ -6,6 +6,7 @@ use crate::storage::{
     Key, Mutation, FOR_UPDATE_TS_PREFIX, MIN_COMMIT_TS_PREFIX, SHORT_VALUE_MAX_LEN,
     SHORT_VALUE_PREFIX, TXN_SIZE_PREFIX,
 };
+use adler::Adler32;
 use byteorder::ReadBytesExt;
 use kvproto::kvrpcpb::{LockInfo, Op};
 use tikv_util::codec::bytes::{self, BytesEncoder};
@@ -24,6 +25,22 @@ const FLAG_DELETE: u8 = b'D';
 const FLAG_LOCK: u8 = b'L';
 const FLAG_PESSIMISTIC: u8 = b'S';
 
+/// Marks the start of the TLV-encoded optional-field section, distinct from
+/// every legacy (v0) flag byte so `parse` can tell the layouts apart.
+const LOCK_VERSION_PREFIX: u8 = b'V';
+/// Format of the TLV section; bump on framing changes (unrelated to adding
+/// new fields, which `parse` already skips via their length prefix).
+const LOCK_FORMAT_VERSION: u8 = 1;
+/// Optional trailing Adler-32 checksum over the preceding bytes, letting
+/// readers detect silent corruption.
+const CHECKSUM_PREFIX: u8 = b'C';
+/// Generation counter, bumped by [`Lock::begin_write`]/[`Lock::end_write`];
+/// even means quiescent, odd means mid-write.
+const GENERATION_PREFIX: u8 = b'G';
+/// Marks an async-commit (or 1PC) lock. Carries no payload; its mere
+/// presence is the signal, checked by [`Lock::is_async_commit`].
+const ASYNC_COMMIT_PREFIX: u8 = b'A';
+
 impl LockType {
     pub fn from_mutation(mutation: &Mutation) -> LockType {
         match *mutation {
@@ -64,6 +81,24 @@ pub struct Lock {
     pub for_update_ts: u64,
     pub txn_size: u64,
     pub min_commit_ts: u64,
+    /// Optional fields this binary doesn't recognize, preserved as
+    /// `(flag, payload)` pairs so `to_bytes` round-trips them unchanged.
+    pub unknown_fields: Vec<(u8, Vec<u8>)>,
+    /// Whether `to_bytes` should append an Adler-32 checksum over the
+    /// record. Set via [`Lock::use_checksum`]; also set by `parse` when the
+    /// decoded record already carried one, so re-encoding preserves it.
+    pub use_checksum: bool,
+    /// Seqlock-style generation counter: even when quiescent, odd mid-write
+    /// (see [`Lock::begin_write`], [`Lock::end_write`], [`Lock::read_validate`]).
+    /// A plain `u64`, not an atomic, so it only validates reads the caller
+    /// already serializes against writes some other way (e.g. a mutex); it
+    /// doesn't by itself make cross-thread access to a `Lock` safe.
+    pub generation: u64,
+    /// Whether this is an async-commit (or 1PC) lock, i.e. one whose
+    /// transaction is guaranteed to commit at or after `min_commit_ts`
+    /// rather than needing a lock-resolution round trip. Set via
+    /// [`Lock::use_async_commit`]; read via [`Lock::is_async_commit`].
+    pub use_async_commit: bool,
 }
 
 impl Lock {
@@ -86,9 +121,59 @@ impl Lock {
             for_update_ts,
             txn_size,
             min_commit_ts,
+            unknown_fields: Vec::new(),
+            use_checksum: false,
+            generation: 0,
+            use_async_commit: false,
         }
     }
 
+    /// Turns on an Adler-32 checksum over this lock's serialized bytes.
+    pub fn use_checksum(mut self) -> Lock {
+        self.use_checksum = true;
+        self
+    }
+
+    /// Begins a mutation of this lock record: bumps `generation` from even
+    /// to odd, so a reader that later calls `read_validate` against it
+    /// knows its snapshot may have been taken mid-write.
+    pub fn begin_write(&mut self) {
+        debug_assert_eq!(self.generation % 2, 0, "begin_write on a mid-write lock");
+        self.generation += 1;
+    }
+
+    /// Ends a mutation started by `begin_write`: bumps `generation` from odd
+    /// back to even, publishing the new state as a consistent snapshot.
+    pub fn end_write(&mut self) {
+        debug_assert_eq!(self.generation % 2, 1, "end_write without begin_write");
+        self.generation += 1;
+    }
+
+    /// True if `prev_gen` is still current: `generation` is even (no writer
+    /// was mid-mutation) and unchanged since (no intervening write).
+    pub fn read_validate(&self, prev_gen: u64) -> bool {
+        self.generation % 2 == 0 && self.generation == prev_gen
+    }
+
+    /// Marks this as an async-commit (or 1PC) lock, whose transaction is
+    /// guaranteed to commit at or after `min_commit_ts`.
+    pub fn use_async_commit(mut self) -> Lock {
+        self.use_async_commit = true;
+        self
+    }
+
+    /// Whether this is an async-commit (or 1PC) lock. See
+    /// [`Lock::check_ts_conflict`] for how this changes read behavior.
+    pub fn is_async_commit(&self) -> bool {
+        self.use_async_commit
+    }
+
+    fn encode_tlv(b: &mut Vec<u8>, flag: u8, payload: &[u8]) {
+        b.push(flag);
+        b.encode_var_u64(payload.len() as u64).unwrap();
+        b.extend_from_slice(payload);
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut b = Vec::with_capacity(
             1 + MAX_VAR_U64_LEN + self.primary.len() + MAX_VAR_U64_LEN + SHORT_VALUE_MAX_LEN + 2,
@@ -97,27 +182,82 @@ impl Lock {
         b.encode_compact_bytes(&self.primary).unwrap();
         b.encode_var_u64(self.ts).unwrap();
         b.encode_var_u64(self.ttl).unwrap();
+
+        let uses_new_features = !self.unknown_fields.is_empty()
+            || self.use_checksum
+            || self.generation > 0
+            || self.use_async_commit;
+
+        if !uses_new_features {
+            // None of this series' new features are in play: keep emitting
+            // the plain v0 fixed-width layout for the pre-existing optional
+            // fields, exactly as before. This is the common case (e.g. every
+            // pessimistic lock sets `for_update_ts`), and it must stay
+            // decodable by a not-yet-upgraded peer during a rolling
+            // upgrade, which doesn't know about `LOCK_VERSION_PREFIX` at all.
+            if let Some(ref v) = self.short_value {
+                b.push(SHORT_VALUE_PREFIX);
+                b.push(v.len() as u8);
+                b.extend_from_slice(v);
+            }
+            if self.for_update_ts > 0 {
+                b.push(FOR_UPDATE_TS_PREFIX);
+                b.encode_u64(self.for_update_ts).unwrap();
+            }
+            if self.txn_size > 0 {
+                b.push(TXN_SIZE_PREFIX);
+                b.encode_u64(self.txn_size).unwrap();
+            }
+            if self.min_commit_ts > 0 {
+                b.push(MIN_COMMIT_TS_PREFIX);
+                b.encode_u64(self.min_commit_ts).unwrap();
+            }
+            return b;
+        }
+
+        b.push(LOCK_VERSION_PREFIX);
+        b.push(LOCK_FORMAT_VERSION);
         if let Some(ref v) = self.short_value {
-            b.push(SHORT_VALUE_PREFIX);
-            b.push(v.len() as u8);
-            b.extend_from_slice(v);
+            Self::encode_tlv(&mut b, SHORT_VALUE_PREFIX, v);
         }
         if self.for_update_ts > 0 {
-            b.push(FOR_UPDATE_TS_PREFIX);
-            b.encode_u64(self.for_update_ts).unwrap();
+            let mut payload = Vec::with_capacity(8);
+            payload.encode_u64(self.for_update_ts).unwrap();
+            Self::encode_tlv(&mut b, FOR_UPDATE_TS_PREFIX, &payload);
         }
         if self.txn_size > 0 {
-            b.push(TXN_SIZE_PREFIX);
-            b.encode_u64(self.txn_size).unwrap();
+            let mut payload = Vec::with_capacity(8);
+            payload.encode_u64(self.txn_size).unwrap();
+            Self::encode_tlv(&mut b, TXN_SIZE_PREFIX, &payload);
         }
         if self.min_commit_ts > 0 {
-            b.push(MIN_COMMIT_TS_PREFIX);
-            b.encode_u64(self.min_commit_ts).unwrap();
+            let mut payload = Vec::with_capacity(8);
+            payload.encode_u64(self.min_commit_ts).unwrap();
+            Self::encode_tlv(&mut b, MIN_COMMIT_TS_PREFIX, &payload);
+        }
+        if self.generation > 0 {
+            let mut payload = Vec::with_capacity(8);
+            payload.encode_u64(self.generation).unwrap();
+            Self::encode_tlv(&mut b, GENERATION_PREFIX, &payload);
+        }
+        if self.use_async_commit {
+            Self::encode_tlv(&mut b, ASYNC_COMMIT_PREFIX, &[]);
+        }
+        for (flag, payload) in &self.unknown_fields {
+            Self::encode_tlv(&mut b, *flag, payload);
+        }
+        if self.use_checksum {
+            let mut adler = Adler32::new();
+            adler.write_slice(&b);
+            let mut payload = Vec::with_capacity(4);
+            payload.encode_u32(adler.checksum()).unwrap();
+            Self::encode_tlv(&mut b, CHECKSUM_PREFIX, &payload);
         }
         b
     }
 
-    pub fn parse(mut b: &[u8]) -> Result<Lock> {
+    pub fn parse(buf: &[u8]) -> Result<Lock> {
+        let mut b = buf;
         if b.is_empty() {
             return Err(Error::BadFormatLock);
         }
@@ -138,27 +278,69 @@ impl Lock {
         let mut for_update_ts = 0;
         let mut txn_size: u64 = 0;
         let mut min_commit_ts: u64 = 0;
-        while !b.is_empty() {
-            match b.read_u8()? {
-                SHORT_VALUE_PREFIX => {
-                    let len = b.read_u8()?;
-                    if b.len() < len as usize {
-                        panic!(
-                            "content len [{}] shorter than short value len [{}]",
-                            b.len(),
-                            len,
-                        );
+        let mut unknown_fields = Vec::new();
+        let mut use_checksum = false;
+        let mut generation: u64 = 0;
+        let mut use_async_commit = false;
+
+        if b[0] == LOCK_VERSION_PREFIX {
+            b.read_u8()?; // consume the version marker
+            b.read_u8()?; // format version; nothing to branch on yet
+            while !b.is_empty() {
+                let entry_start = buf.len() - b.len();
+                let flag = b.read_u8()?;
+                let len = number::decode_var_u64(&mut b)? as usize;
+                if b.len() < len {
+                    return Err(Error::BadFormatLock);
+                }
+                let payload = &b[..len];
+                b = &b[len..];
+                match flag {
+                    SHORT_VALUE_PREFIX => short_value = Some(payload.to_vec()),
+                    FOR_UPDATE_TS_PREFIX => for_update_ts = number::decode_u64(&mut &*payload)?,
+                    TXN_SIZE_PREFIX => txn_size = number::decode_u64(&mut &*payload)?,
+                    MIN_COMMIT_TS_PREFIX => min_commit_ts = number::decode_u64(&mut &*payload)?,
+                    GENERATION_PREFIX => generation = number::decode_u64(&mut &*payload)?,
+                    ASYNC_COMMIT_PREFIX => use_async_commit = true,
+                    CHECKSUM_PREFIX => {
+                        let want = number::decode_u32(&mut &*payload)?;
+                        let mut adler = Adler32::new();
+                        adler.write_slice(&buf[..entry_start]);
+                        if adler.checksum() != want {
+                            return Err(Error::LockChecksumMismatch);
+                        }
+                        use_checksum = true;
+                    }
+                    // An optional field this binary doesn't understand yet.
+                    // Its length prefix tells us exactly how many bytes to
+                    // skip, so we can preserve it instead of rejecting the
+                    // whole record.
+                    flag => unknown_fields.push((flag, payload.to_vec())),
+                }
+            }
+        } else {
+            // Legacy (v0) layout: no version marker, fixed-width optional
+            // fields, no forward compatibility. Kept so already-written
+            // records keep decoding unchanged.
+            while !b.is_empty() {
+                match b.read_u8()? {
+                    SHORT_VALUE_PREFIX => {
+                        let len = b.read_u8()?;
+                        if b.len() < len as usize {
+                            return Err(Error::BadFormatLock);
+                        }
+                        short_value = Some(b[..len as usize].to_vec());
+                        b = &b[len as usize..];
                     }
-                    short_value = Some(b[..len as usize].to_vec());
-                    b = &b[len as usize..];
+                    FOR_UPDATE_TS_PREFIX => for_update_ts = number::decode_u64(&mut b)?,
+                    TXN_SIZE_PREFIX => txn_size = number::decode_u64(&mut b)?,
+                    MIN_COMMIT_TS_PREFIX => min_commit_ts = number::decode_u64(&mut b)?,
+                    _ => return Err(Error::BadFormatLock),
                 }
-                FOR_UPDATE_TS_PREFIX => for_update_ts = number::decode_u64(&mut b)?,
-                TXN_SIZE_PREFIX => txn_size = number::decode_u64(&mut b)?,
-                MIN_COMMIT_TS_PREFIX => min_commit_ts = number::decode_u64(&mut b)?,
-                flag => panic!("invalid flag [{}] in lock", flag),
             }
         }
-        Ok(Lock::new(
+
+        Ok(Lock {
             lock_type,
             primary,
             ts,
@@ -167,7 +349,11 @@ impl Lock {
             for_update_ts,
             txn_size,
             min_commit_ts,
-        ))
+            unknown_fields,
+            use_checksum,
+            generation,
+            use_async_commit,
+        })
     }
 
     pub fn into_lock_info(self, raw_key: Vec<u8>) -> LockInfo {
@@ -188,17 +374,40 @@ impl Lock {
     }
 
     /// Checks whether the lock conflicts with the given `ts`. If `ts == MaxU64`, the primary lock will be ignored.
-    pub fn check_ts_conflict(self, key: &Key, ts: u64, bypass_locks: &TsSet) -> Result<()> {
+    ///
+    /// `prev_gen`, if set, is the generation observed before starting to
+    /// read this lock (see [`Lock::read_validate`]); if it no longer
+    /// matches, returns `Error::LockGenerationChanged` instead of a
+    /// possibly-torn decision, and the caller should retry.
+    ///
+    /// For an async-commit lock (see [`Lock::is_async_commit`]), a reader
+    /// with `ts < min_commit_ts` can proceed without waiting; otherwise it
+    /// gets back a `min_commit_ts` to push past `ts` instead of blocking
+    /// (`ts + 1`, saturating at `u64::MAX`, so for `ts == u64::MAX` the
+    /// returned value equals `ts`). See [`CheckLockResult`].
+    pub fn check_ts_conflict(
+        self,
+        key: &Key,
+        ts: u64,
+        bypass_locks: &TsSet,
+        prev_gen: Option<u64>,
+    ) -> Result<CheckLockResult> {
+        if let Some(prev_gen) = prev_gen {
+            if !self.read_validate(prev_gen) {
+                return Err(Error::LockGenerationChanged);
+            }
+        }
+
         if self.ts > ts
             || self.lock_type == LockType::Lock
             || self.lock_type == LockType::Pessimistic
         {
             // Ignore lock when lock.ts > ts or lock's type is Lock or Pessimistic
-            return Ok(());
+            return Ok(CheckLockResult::Clean);
         }
 
         if bypass_locks.contains(self.ts) {
-            return Ok(());
+            return Ok(CheckLockResult::Clean);
         }
 
         let raw_key = key.to_raw()?;
@@ -206,14 +415,42 @@ impl Lock {
         if ts == std::u64::MAX && raw_key == self.primary {
             // When `ts == u64::MAX` (which means to get latest committed version for
             // primary key), and current key is the primary key, we ignore this lock.
-            return Ok(());
+            return Ok(CheckLockResult::Clean);
+        }
+
+        if self.is_async_commit() {
+            if ts < self.min_commit_ts {
+                // The transaction is guaranteed to commit at or after
+                // `min_commit_ts`, which is after `ts`: this read can't
+                // observe it either way, so it's safe to proceed.
+                return Ok(CheckLockResult::Clean);
+            }
+            // Ask the lock owner to push `min_commit_ts` past `ts` instead
+            // of making the reader wait or resolve the lock. Saturate
+            // instead of overflowing: `ts == u64::MAX` is reachable here via
+            // the "latest committed version" read of a secondary key, which
+            // the primary-key short-circuit above doesn't cover.
+            return Ok(CheckLockResult::PushMinCommitTs(ts.saturating_add(1)));
         }
 
         // There is a pending lock. Client should wait or clean it.
-        Err(Error::KeyIsLocked(self.into_lock_info(raw_key)))
+        Ok(CheckLockResult::Locked(self.into_lock_info(raw_key)))
     }
 }
 
+/// The outcome of [`Lock::check_ts_conflict`].
+#[derive(PartialEq, Clone, Debug)]
+pub enum CheckLockResult {
+    /// No conflicting lock; the read can proceed.
+    Clean,
+    /// The lock is an async-commit lock whose `min_commit_ts` should be
+    /// advanced to at least this value; the read can then proceed without
+    /// waiting on a full lock resolution.
+    PushMinCommitTs(u64),
+    /// A conflicting lock exists; the client should wait or resolve it.
+    Locked(LockInfo),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -343,6 +580,128 @@ mod tests {
         assert!(Lock::parse(&v[..4]).is_err());
     }
 
+    #[test]
+    fn test_lock_legacy_fields_keep_v0_layout() {
+        // Setting only pre-existing optional fields (as every pessimistic
+        // lock does via `for_update_ts`) must not switch to the new
+        // `LOCK_VERSION_PREFIX` layout: an un-upgraded peer during a rolling
+        // upgrade can only decode the v0 layout.
+        let lock = Lock::new(
+            LockType::Put,
+            b"pk".to_vec(),
+            1,
+            10,
+            Some(b"short_value".to_vec()),
+            10,
+            16,
+            20,
+        );
+        let v = lock.to_bytes();
+
+        // Reconstruct the plain v0 layout by hand and compare byte-for-byte,
+        // rather than merely checking for the absence of the version marker
+        // (which could coincidentally appear inside `short_value`).
+        let mut expected = Vec::new();
+        expected.push(FLAG_PUT);
+        expected.encode_compact_bytes(b"pk").unwrap();
+        expected.encode_var_u64(1).unwrap();
+        expected.encode_var_u64(10).unwrap();
+        expected.push(SHORT_VALUE_PREFIX);
+        expected.push(b"short_value".len() as u8);
+        expected.extend_from_slice(b"short_value");
+        expected.push(FOR_UPDATE_TS_PREFIX);
+        expected.encode_u64(10).unwrap();
+        expected.push(TXN_SIZE_PREFIX);
+        expected.encode_u64(16).unwrap();
+        expected.push(MIN_COMMIT_TS_PREFIX);
+        expected.encode_u64(20).unwrap();
+
+        assert_eq!(v, expected);
+        assert_eq!(Lock::parse(&v).unwrap(), lock);
+    }
+
+    #[test]
+    fn test_lock_parse_legacy_short_value_overrun_is_err() {
+        // A v0-layout record whose short-value length claims more bytes
+        // than remain in the buffer must be rejected, not panic.
+        let mut v = Vec::new();
+        v.push(FLAG_PUT);
+        v.encode_compact_bytes(b"pk").unwrap();
+        v.encode_var_u64(1).unwrap();
+        v.encode_var_u64(10).unwrap();
+        v.push(SHORT_VALUE_PREFIX);
+        v.push(255); // claims 255 bytes of short value; none follow
+        assert!(Lock::parse(&v).is_err());
+    }
+
+    #[test]
+    fn test_lock_parse_legacy_bad_flag_is_err() {
+        // A v0-layout record with an optional-field flag this binary
+        // doesn't recognize must be rejected, not panic: the legacy
+        // layout has no length prefix to skip unknown fields by.
+        let mut v = Vec::new();
+        v.push(FLAG_PUT);
+        v.encode_compact_bytes(b"pk").unwrap();
+        v.encode_var_u64(1).unwrap();
+        v.encode_var_u64(10).unwrap();
+        v.push(b'?');
+        assert!(Lock::parse(&v).is_err());
+    }
+
+    #[test]
+    fn test_lock_unknown_fields_round_trip() {
+        // A record written by a newer binary carries a field this one
+        // doesn't recognize; it must be preserved rather than dropped.
+        let mut lock = Lock::new(LockType::Put, b"pk".to_vec(), 1, 10, None, 0, 0, 0);
+        lock.unknown_fields.push((b'Z', b"future".to_vec()));
+
+        let v = lock.to_bytes();
+        let parsed = Lock::parse(&v).unwrap();
+        assert_eq!(parsed, lock);
+        assert_eq!(parsed.unknown_fields, vec![(b'Z', b"future".to_vec())]);
+    }
+
+    #[test]
+    fn test_lock_parse_truncated_tlv_is_err() {
+        // A TLV entry whose length prefix claims more bytes than are left
+        // in the buffer must be rejected, not cause an out-of-bounds panic.
+        let mut lock = Lock::new(LockType::Put, b"pk".to_vec(), 1, 10, None, 0, 0, 0);
+        lock.unknown_fields.push((b'Z', b"future".to_vec()));
+        let mut v = lock.to_bytes();
+        let len = v.len();
+        v.truncate(len - 1);
+        assert!(Lock::parse(&v).is_err());
+    }
+
+    #[test]
+    fn test_lock_checksum() {
+        let lock = Lock::new(
+            LockType::Put,
+            b"pk".to_vec(),
+            1,
+            10,
+            Some(b"short_value".to_vec()),
+            0,
+            0,
+            0,
+        )
+        .use_checksum();
+
+        let v = lock.to_bytes();
+        let parsed = Lock::parse(&v).unwrap();
+        assert_eq!(parsed, lock);
+        assert!(parsed.use_checksum);
+
+        // Flip a byte in the payload; the checksum must catch it.
+        let mut corrupted = v;
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+        match Lock::parse(&corrupted) {
+            Err(Error::LockChecksumMismatch) => {}
+            other => panic!("expected LockChecksumMismatch, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_check_ts_conflict() {
         let key = Key::from_raw(b"foo");
@@ -351,44 +710,179 @@ mod tests {
         let empty = Default::default();
 
         // Ignore the lock if read ts is less than the lock version
-        lock.clone().check_ts_conflict(&key, 50, &empty).unwrap();
+        assert_eq!(
+            lock.clone()
+                .check_ts_conflict(&key, 50, &empty, None)
+                .unwrap(),
+            CheckLockResult::Clean
+        );
 
         // Returns the lock if read ts >= lock version
-        lock.clone()
-            .check_ts_conflict(&key, 110, &empty)
-            .unwrap_err();
+        assert!(matches!(
+            lock.clone()
+                .check_ts_conflict(&key, 110, &empty, None)
+                .unwrap(),
+            CheckLockResult::Locked(_)
+        ));
 
         // Ignore locks that occurs in the `bypass_locks` set.
-        lock.clone()
-            .check_ts_conflict(&key, 110, &TsSet::new(vec![109]))
-            .unwrap_err();
-        lock.clone()
-            .check_ts_conflict(&key, 110, &TsSet::new(vec![110]))
-            .unwrap_err();
-        lock.clone()
-            .check_ts_conflict(&key, 110, &TsSet::new(vec![100]))
-            .unwrap();
-        lock.clone()
-            .check_ts_conflict(&key, 110, &TsSet::new(vec![99, 101, 102, 100, 80]))
-            .unwrap();
+        assert!(matches!(
+            lock.clone()
+                .check_ts_conflict(&key, 110, &TsSet::new(vec![109]), None)
+                .unwrap(),
+            CheckLockResult::Locked(_)
+        ));
+        assert!(matches!(
+            lock.clone()
+                .check_ts_conflict(&key, 110, &TsSet::new(vec![110]), None)
+                .unwrap(),
+            CheckLockResult::Locked(_)
+        ));
+        assert_eq!(
+            lock.clone()
+                .check_ts_conflict(&key, 110, &TsSet::new(vec![100]), None)
+                .unwrap(),
+            CheckLockResult::Clean
+        );
+        assert_eq!(
+            lock.clone()
+                .check_ts_conflict(&key, 110, &TsSet::new(vec![99, 101, 102, 100, 80]), None)
+                .unwrap(),
+            CheckLockResult::Clean
+        );
 
         // Ignore the lock if it is Lock or Pessimistic.
         lock.lock_type = LockType::Lock;
-        lock.clone().check_ts_conflict(&key, 110, &empty).unwrap();
+        assert_eq!(
+            lock.clone()
+                .check_ts_conflict(&key, 110, &empty, None)
+                .unwrap(),
+            CheckLockResult::Clean
+        );
         lock.lock_type = LockType::Pessimistic;
-        lock.clone().check_ts_conflict(&key, 110, &empty).unwrap();
+        assert_eq!(
+            lock.clone()
+                .check_ts_conflict(&key, 110, &empty, None)
+                .unwrap(),
+            CheckLockResult::Clean
+        );
 
         // Ignore the primary lock when reading the latest committed version by setting u64::MAX as ts
         lock.lock_type = LockType::Put;
         lock.primary = b"foo".to_vec();
-        lock.clone()
-            .check_ts_conflict(&key, std::u64::MAX, &empty)
-            .unwrap();
+        assert_eq!(
+            lock.clone()
+                .check_ts_conflict(&key, std::u64::MAX, &empty, None)
+                .unwrap(),
+            CheckLockResult::Clean
+        );
 
         // Should not ignore the secondary lock even though reading the latest version
         lock.primary = b"bar".to_vec();
-        lock.clone()
-            .check_ts_conflict(&key, std::u64::MAX, &empty)
-            .unwrap_err();
+        assert!(matches!(
+            lock.clone()
+                .check_ts_conflict(&key, std::u64::MAX, &empty, None)
+                .unwrap(),
+            CheckLockResult::Locked(_)
+        ));
+    }
+
+    #[test]
+    fn test_check_ts_conflict_async_commit() {
+        let key = Key::from_raw(b"foo");
+        let lock =
+            Lock::new(LockType::Put, b"foo".to_vec(), 100, 3, None, 0, 0, 120).use_async_commit();
+        let empty = Default::default();
+
+        // read_ts < min_commit_ts: the transaction is guaranteed to commit
+        // after read_ts, so the read can proceed without waiting.
+        assert_eq!(
+            lock.clone()
+                .check_ts_conflict(&key, 110, &empty, None)
+                .unwrap(),
+            CheckLockResult::Clean
+        );
+
+        // read_ts >= min_commit_ts: ask for a push instead of blocking.
+        assert_eq!(
+            lock.clone()
+                .check_ts_conflict(&key, 120, &empty, None)
+                .unwrap(),
+            CheckLockResult::PushMinCommitTs(121)
+        );
+        assert_eq!(
+            lock.clone()
+                .check_ts_conflict(&key, 130, &empty, None)
+                .unwrap(),
+            CheckLockResult::PushMinCommitTs(131)
+        );
+    }
+
+    #[test]
+    fn test_check_ts_conflict_async_commit_max_ts_does_not_overflow() {
+        // Reading the latest committed version of a *secondary* key (ts ==
+        // u64::MAX) against an async-commit lock must not overflow when
+        // computing the pushed min_commit_ts.
+        let key = Key::from_raw(b"foo");
+        let lock = Lock::new(LockType::Put, b"primary".to_vec(), 100, 3, None, 0, 0, 120)
+            .use_async_commit();
+        let empty = Default::default();
+
+        assert_eq!(
+            lock.check_ts_conflict(&key, std::u64::MAX, &empty, None)
+                .unwrap(),
+            CheckLockResult::PushMinCommitTs(std::u64::MAX)
+        );
+    }
+
+    #[test]
+    fn test_lock_generation_round_trip() {
+        let mut lock = Lock::new(LockType::Put, b"pk".to_vec(), 1, 10, None, 0, 0, 0);
+        lock.begin_write();
+        lock.end_write();
+        assert_eq!(lock.generation, 2);
+
+        let v = lock.to_bytes();
+        let parsed = Lock::parse(&v).unwrap();
+        assert_eq!(parsed, lock);
+        assert_eq!(parsed.generation, 2);
+    }
+
+    #[test]
+    fn test_lock_seqlock_read_validate() {
+        let mut lock = Lock::new(LockType::Put, b"pk".to_vec(), 1, 10, None, 0, 0, 0);
+        assert!(lock.read_validate(0));
+
+        lock.begin_write();
+        // Mid-write, the generation is odd: no read can validate against it.
+        assert!(!lock.read_validate(0));
+        assert!(!lock.read_validate(1));
+
+        lock.end_write();
+        assert_eq!(lock.generation, 2);
+        assert!(lock.read_validate(2));
+        // A reader that snapshotted the old generation must retry.
+        assert!(!lock.read_validate(0));
+    }
+
+    #[test]
+    fn test_check_ts_conflict_generation_retry() {
+        let key = Key::from_raw(b"foo");
+        let mut lock = Lock::new(LockType::Put, vec![], 100, 3, None, 0, 1, 0);
+        let empty = Default::default();
+
+        lock.begin_write();
+        match lock.clone().check_ts_conflict(&key, 50, &empty, Some(0)) {
+            Err(Error::LockGenerationChanged) => {}
+            other => panic!("expected LockGenerationChanged, got {:?}", other),
+        }
+
+        lock.end_write();
+        assert_eq!(
+            lock.clone()
+                .check_ts_conflict(&key, 50, &empty, Some(lock.generation))
+                .unwrap(),
+            CheckLockResult::Clean
+        );
     }
 }
@@ -0,0 +1,67 @@
+// Copyright 2016 TiKV Project Authors. Licensed under Apache-2.0.
+
+mod lock;
+
+pub use self::lock::{CheckLockResult, Lock, LockType};
+
+use std::error;
+use std::io;
+
+use kvproto::kvrpcpb::LockInfo;
+use quick_error::quick_error;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum Error {
+        Io(err: io::Error) {
+            from()
+            cause(err)
+            display("{}", err)
+        }
+        Codec(err: tikv_util::codec::Error) {
+            from()
+            cause(err)
+            display("{}", err)
+        }
+        KeyIsLocked(info: LockInfo) {
+            display("key is locked (backoff or cleanup) {:?}", info)
+        }
+        BadFormatLock {
+            display("bad format lock data")
+        }
+        /// Returned by `Lock::parse` when a record's checksum (see
+        /// `CHECKSUM_PREFIX` in `lock.rs`) doesn't match the stored one.
+        LockChecksumMismatch {
+            display("lock data checksum mismatch")
+        }
+        /// Returned by `Lock::check_ts_conflict` when a caller-supplied
+        /// generation (see `Lock::read_validate`) no longer matches: the
+        /// read may have seen a torn record, so the caller should retry.
+        LockGenerationChanged {
+            display("lock generation changed since the read started, retry")
+        }
+        Other(err: Box<dyn error::Error + Sync + Send>) {
+            from()
+            cause(err.as_ref())
+            display("{:?}", err)
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A set of lock timestamps (a lock's `ts`, its transaction's start
+/// timestamp) whose locks should be bypassed when checking a key for lock
+/// conflicts, e.g. the reader's own in-flight transactions.
+#[derive(Clone, Debug, Default)]
+pub struct TsSet(Vec<u64>);
+
+impl TsSet {
+    pub fn new(ts: Vec<u64>) -> TsSet {
+        TsSet(ts)
+    }
+
+    pub fn contains(&self, ts: u64) -> bool {
+        self.0.contains(&ts)
+    }
+}